@@ -0,0 +1,79 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand_core::{OsRng, RngCore};
+use sqlx::Sqlite;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SESSION_TTL_SECS: i64 = 24 * 60 * 60;
+const MAX_PASSWORD_BYTES: usize = 256;
+
+/// Hashes `password` into a PHC string, or `Err` if the password is empty,
+/// too long, or Argon2 otherwise rejects it — never panics on attacker
+/// input.
+pub fn hash_password(password: &str) -> Result<String, &'static str> {
+    if password.is_empty() || password.len() > MAX_PASSWORD_BYTES {
+        return Err("password must be between 1 and 256 bytes");
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|_| "could not hash password")
+}
+
+pub fn verify_password(password: &str, phc: &str) -> bool {
+    match PasswordHash::new(phc) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn new_session_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub async fn create_session(pool: &sqlx::Pool<Sqlite>, user_id: u32) -> Result<String, sqlx::Error> {
+    let token = new_session_token();
+    let expires_at = now_secs() + SESSION_TTL_SECS;
+    sqlx::query("INSERT INTO sessions (token, user_id, expires_at) VALUES (?, ?, ?)")
+        .bind(&token)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+    Ok(token)
+}
+
+/// Returns the session's user id, or `None` if the token is unknown or expired.
+pub async fn session_user_id(pool: &sqlx::Pool<Sqlite>, token: &str) -> Option<u32> {
+    let row: Option<(u32, i64)> = sqlx::query_as("SELECT user_id, expires_at FROM sessions WHERE token = ?")
+        .bind(token)
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten();
+
+    match row {
+        Some((user_id, expires_at)) if expires_at > now_secs() => Some(user_id),
+        _ => None,
+    }
+}
+
+/// Pulls the `session` cookie value out of a request's parsed headers.
+pub fn extract_session_cookie(headers: &HashMap<String, String>) -> Option<String> {
+    let cookie_header = headers.get("cookie")?;
+    for part in cookie_header.split(';') {
+        if let Some(v) = part.trim().strip_prefix("session=") {
+            return Some(v.to_string());
+        }
+    }
+    None
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
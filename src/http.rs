@@ -0,0 +1,111 @@
+//! Minimal HTTP/1.1 parsing: reads the request line, headers, and a
+//! `Content-Length`-sized body (looping until it's all arrived, capped so a
+//! client can't exhaust memory with a bogus length), and tells the caller
+//! whether the connection should be kept alive for another request.
+
+use std::collections::HashMap;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+const MAX_HEADER_BYTES: usize = 8192;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const READ_CHUNK: usize = 4096;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub version: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Reads one request off `stream`, starting from any bytes already buffered
+/// in `carry` (leftover from a previous call on the same keep-alive
+/// connection). Returns `Ok(None)` if the client closed the connection
+/// before the headers were complete, or sent a malformed request line,
+/// oversized headers, or a `Content-Length` above `MAX_BODY_BYTES`. Anything
+/// read past the end of this request's body is left in `carry` for the next
+/// call.
+pub async fn read_request(stream: &mut TcpStream, carry: &mut Vec<u8>) -> std::io::Result<Option<Request>> {
+    let mut buf = std::mem::take(carry);
+    let header_end = loop {
+        if let Some(pos) = find_header_end(&buf) {
+            break pos;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Ok(None);
+        }
+
+        let mut chunk = [0u8; READ_CHUNK];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let header_str = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = header_str.split("\r\n");
+
+    let request_line = lines.next().unwrap_or("");
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let raw_path = parts.next().unwrap_or("").to_string();
+    let version = parts.next().unwrap_or("HTTP/1.1").to_string();
+    if method.is_empty() || raw_path.is_empty() {
+        return Ok(None);
+    }
+
+    let (path, query) = match raw_path.split_once('?') {
+        Some((p, q)) => (p.to_string(), q.to_string()),
+        None => (raw_path, String::new()),
+    };
+
+    let mut headers = HashMap::new();
+    for line in lines {
+        if let Some((k, v)) = line.split_once(':') {
+            headers.insert(k.trim().to_ascii_lowercase(), v.trim().to_string());
+        }
+    }
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_BYTES {
+        return Ok(None);
+    }
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        if body.len() > MAX_BODY_BYTES {
+            return Ok(None);
+        }
+
+        let mut chunk = [0u8; READ_CHUNK];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    if body.len() > content_length {
+        *carry = body.split_off(content_length);
+    }
+
+    Ok(Some(Request { method, path, query, version, headers, body }))
+}
+
+/// HTTP/1.1 keeps the connection open by default; HTTP/1.0 closes by
+/// default. Either can be overridden by an explicit `Connection` header.
+pub fn is_keep_alive(req: &Request) -> bool {
+    match req.headers.get("connection") {
+        Some(v) => v.eq_ignore_ascii_case("keep-alive"),
+        None => req.version == "HTTP/1.1",
+    }
+}
+
+fn find_header_end(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|p| p + 4)
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use sqlx::Sqlite;
+use tokio::sync::{oneshot, Mutex};
+use tokio::time::{sleep, Duration};
+
+use crate::User;
+
+const BATCH_WINDOW: Duration = Duration::from_millis(1);
+const MAX_BATCH_SIZE: usize = 64;
+
+/// Coalesces concurrent user lookups by id into a single `IN (...)` query.
+pub struct Loader {
+    pool: Arc<sqlx::Pool<Sqlite>>,
+    pending: Mutex<Vec<(u32, oneshot::Sender<Option<User>>)>>,
+}
+
+impl Loader {
+    pub fn new(pool: Arc<sqlx::Pool<Sqlite>>) -> Arc<Self> {
+        Arc::new(Loader {
+            pool,
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub async fn load(self: &Arc<Self>, id: u32) -> Option<User> {
+        let (tx, rx) = oneshot::channel();
+        let spawn_timer = {
+            let mut pending = self.pending.lock().await;
+            pending.push((id, tx));
+            if pending.len() >= MAX_BATCH_SIZE {
+                let batch = std::mem::take(&mut *pending);
+                drop(pending);
+                self.flush(batch).await;
+                false
+            } else {
+                pending.len() == 1
+            }
+        };
+
+        if spawn_timer {
+            let loader = Arc::clone(self);
+            tokio::spawn(async move {
+                sleep(BATCH_WINDOW).await;
+                let batch = {
+                    let mut pending = loader.pending.lock().await;
+                    std::mem::take(&mut *pending)
+                };
+                if !batch.is_empty() {
+                    loader.flush(batch).await;
+                }
+            });
+        }
+
+        rx.await.unwrap_or(None)
+    }
+
+    async fn flush(&self, batch: Vec<(u32, oneshot::Sender<Option<User>>)>) {
+        let ids: Vec<u32> = batch.iter().map(|(id, _)| *id).collect();
+        let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        let query = format!("SELECT * FROM users WHERE id IN ({})", placeholders);
+
+        let mut q = sqlx::query_as::<_, User>(&query);
+        for id in &ids {
+            q = q.bind(id);
+        }
+
+        let rows: HashMap<u32, User> = match q.fetch_all(self.pool.as_ref()).await {
+            Ok(rows) => rows.into_iter().map(|u| (u.id, u)).collect(),
+            Err(e) => {
+                println!("loader batch query failed: {}", e);
+                HashMap::new()
+            },
+        };
+
+        for (id, tx) in batch {
+            let _ = tx.send(rows.get(&id).cloned());
+        }
+    }
+}
@@ -1,36 +1,50 @@
+mod auth;
+mod http;
+mod loader;
+mod response;
+mod sqids;
+
 use std::collections::HashMap;
 use std::error::Error;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::sync::{Arc, mpsc};
+use std::sync::Arc;
 use sqlx::migrate::MigrateDatabase;
 use sqlx::{Sqlite, SqlitePool};
-use tokio::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+
+use http::Request;
+use loader::Loader;
+
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() {
-    let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:8080").await.unwrap();
     println!("Listening for connections on port {}", 8080);
 
-    let db_pool = Arc::new(new_conn("sqlite://sqlite1.db").await.unwrap());  
+    let db_pool = Arc::new(new_conn("sqlite://sqlite1.db").await.unwrap());
+    let loader = Loader::new(Arc::clone(&db_pool));
 
-    let (tx, rx) = mpsc::channel();
-    let rx = Arc::new(Mutex::new(rx));
+    let (tx, rx) = mpsc::channel(1024);
+    let rx = Arc::new(tokio::sync::Mutex::new(rx));
 
     for _ in 0..16 {
         let rx = Arc::clone(&rx);
         let db_pool = Arc::clone(&db_pool);
+        let loader = Arc::clone(&loader);
         tokio::spawn(async move {
-            worker(rx, db_pool).await;
+            worker(rx, db_pool, loader).await;
         });
     }
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _addr)) => {
                 let tx = tx.clone();
                 tokio::spawn(async move {
-                    tx.send(stream).unwrap();
+                    let _ = tx.send(stream).await;
                 });
             },
             Err(e) => println!("Unable to connect: {}", e),
@@ -40,70 +54,176 @@ async fn main() {
 }
 
 #[inline]
-async fn handle_connection(mut stream: TcpStream, pool: Arc<sqlx::Pool<Sqlite>>) {
-    let mut buf = [0u8; 4096];
-    match stream.read(&mut buf) {
-        Ok(_) => {
-            let req_str = String::from_utf8_lossy(&mut buf);
-            let parts: Vec<&str> = req_str.split_whitespace().collect();
-    
-            if parts.len() < 1 {
-                println!("something went wrong [{:?}]", req_str);
-                return ;
+async fn handle_connection(mut stream: TcpStream, pool: Arc<sqlx::Pool<Sqlite>>, loader: Arc<Loader>) {
+    let mut carry = Vec::new();
+    loop {
+        let req = match tokio::time::timeout(KEEP_ALIVE_TIMEOUT, http::read_request(&mut stream, &mut carry)).await {
+            Ok(Ok(Some(req))) => req,
+            Ok(Ok(None)) => return,
+            Ok(Err(e)) => {
+                println!("Unable to read stream: {}", e);
+                return;
+            },
+            Err(_) => return, // keep-alive timeout, client went quiet
+        };
+
+        let keep_alive = http::is_keep_alive(&req);
+        route(&mut stream, &req, &pool, &loader).await;
+
+        if !keep_alive {
+            return;
+        }
+    }
+}
+
+async fn route(stream: &mut TcpStream, req: &Request, pool: &Arc<sqlx::Pool<Sqlite>>, loader: &Arc<Loader>) {
+    match req.path.as_str() {
+        "/" => {
+            let body = response::json_body(&response::ApiResponse { data: MessageDto { message: "Hola".to_string() } });
+            send_response(stream, &response::json_response("HTTP/1.1 200 OK", &body)).await;
+        },
+        "/get" => { // GET /get?id=U9xK2 HTTP/1.1
+            let session_ok = match auth::extract_session_cookie(&req.headers) {
+                Some(token) => auth::session_user_id(pool.as_ref(), &token).await.is_some(),
+                None => false,
+            };
+            if !session_ok {
+                send_response(stream, &response::error_response("HTTP/1.1 401 UNAUTHORIZED", "unauthorized")).await;
+                return;
             }
 
-            let pt = parts[1];
-            if pt.len() == 0 {
-                println!("something went wrong [{:?}]", pt);
-                return ;
+            let uid = parse_query_string(&req.query).get("id")
+                .and_then(|v| sqids::decode(v).first().copied())
+                .and_then(|v| u32::try_from(v).ok());
+            if let Some(uid) = uid {
+                match loader.load(uid).await {
+                    Some(u) => {
+                        let dto = UserDto { id: sqids::encode(&[u.id as u64]), name: u.name };
+                        let body = response::json_body(&response::ApiResponse { data: dto });
+                        send_response(stream, &response::json_response("HTTP/1.1 200 OK", &body)).await;
+                    },
+                    None => {
+                        send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "user not found")).await;
+                    },
+                }
+            } else {
+                send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "missing or invalid id")).await;
             }
-            let path: &str = &pt[..parts[1].find('?').unwrap_or(pt.len())];
-
-            match path {
-                "/" => send_response(stream, b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=UTF-8\r\n\r\n<html><body>Hola</body></html>\r\n"),
-                "/get" => { // GET / 127.0.0.1:8080/get?name=hehe&id=3 HTTP/1.1
-                    let req_params = req_str.lines().next().unwrap_or("").split_whitespace().nth(1).unwrap_or("");
-                    if let Some(uid) = parse_query_string(req_params).get("id") {
-                        match sqlx::query_as::<_, User>("SELECT * FROM users where id = $1").bind(uid).fetch_one(pool.as_ref()).await {
-                            Ok(u) => {
-                                let rsp = format!("HTTP/1.1 200 OK\r\nContent-Type: text/json; charset=UTF-8\r\n\r\nname - {}\nid - {}\r\n", u.name, u.id);
-                                send_response(stream, rsp.as_bytes());
-                            },
-                            Err(e) => {
-                                send_response(stream, b"HTTP/1.1 400 BAD REQUEST\r\nContent-Type: text/html; charset=UTF-8\r\n\r\n<html><body>400 Bad Request</body></html>\r\n");
-                                println!("/get error: {}", e);
-                            },
-                        }
-                    } else {
-                        send_response(stream, b"HTTP/1.1 400 BAD REQUEST\r\nContent-Type: text/html; charset=UTF-8\r\n\r\n<html><body>400 Bad Request</body></html>\r\n");
+        },
+        "/users" if req.method == "POST" => {
+            match serde_json::from_slice::<NewUser>(&req.body) {
+                Ok(new_user) => {
+                    match sqlx::query("INSERT INTO users (name) VALUES (?)").bind(&new_user.name).execute(pool.as_ref()).await {
+                        Ok(result) => {
+                            let dto = UserDto { id: sqids::encode(&[result.last_insert_rowid() as u64]), name: new_user.name };
+                            let body = response::json_body(&response::ApiResponse { data: dto });
+                            send_response(stream, &response::json_response("HTTP/1.1 201 CREATED", &body)).await;
+                        },
+                        Err(e) => {
+                            send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "could not create user")).await;
+                            println!("/users insert error: {}", e);
+                        },
+                    }
+                },
+                Err(e) => {
+                    send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "invalid JSON body")).await;
+                    println!("/users parse error: {}", e);
+                },
+            }
+        },
+        "/register" if req.method == "POST" => {
+            match serde_json::from_slice::<RegisterRequest>(&req.body) {
+                Ok(creds) => {
+                    match auth::hash_password(&creds.password) {
+                        Ok(secret) => {
+                            match sqlx::query("INSERT INTO users (username, secret, name) VALUES (?, ?, ?)")
+                                .bind(&creds.username)
+                                .bind(secret.as_bytes())
+                                .bind(&creds.username)
+                                .execute(pool.as_ref())
+                                .await
+                            {
+                                Ok(_) => {
+                                    let body = response::json_body(&response::ApiResponse { data: MessageDto { message: "registered".to_string() } });
+                                    send_response(stream, &response::json_response("HTTP/1.1 200 OK", &body)).await;
+                                },
+                                Err(e) => {
+                                    send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "could not register user")).await;
+                                    println!("/register error: {}", e);
+                                },
+                            }
+                        },
+                        Err(msg) => {
+                            send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", msg)).await;
+                        },
                     }
                 },
-                "/health" => {
-                    match sqlx::query("SELECT 1").fetch_one(pool.as_ref()).await {
-                        Ok(_) => send_response(stream, b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=UTF-8\r\n\r\nHealthy\r\n"),
+                Err(e) => {
+                    send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "invalid JSON body")).await;
+                    println!("/register parse error: {}", e);
+                },
+            }
+        },
+        "/login" if req.method == "POST" => {
+            match serde_json::from_slice::<LoginRequest>(&req.body) {
+                Ok(creds) => {
+                    let row: Result<(u32, Vec<u8>), sqlx::Error> = sqlx::query_as("SELECT id, secret FROM users WHERE username = ?")
+                        .bind(&creds.username)
+                        .fetch_one(pool.as_ref())
+                        .await;
+                    match row {
+                        Ok((user_id, secret)) if auth::verify_password(&creds.password, &String::from_utf8_lossy(&secret)) => {
+                            match auth::create_session(pool.as_ref(), user_id).await {
+                                Ok(token) => {
+                                    let body = response::json_body(&response::ApiResponse { data: MessageDto { message: "logged in".to_string() } });
+                                    let cookie_header = format!("Set-Cookie: session={}; HttpOnly; Path=/", token);
+                                    send_response(stream, &response::json_response_with_headers("HTTP/1.1 200 OK", &[cookie_header], &body)).await;
+                                },
+                                Err(e) => {
+                                    send_response(stream, &response::error_response("HTTP/1.1 500 INTERNAL SERVER ERROR", "could not create session")).await;
+                                    println!("/login session error: {}", e);
+                                },
+                            }
+                        },
+                        Ok(_) => send_response(stream, &response::error_response("HTTP/1.1 401 UNAUTHORIZED", "invalid username or password")).await,
                         Err(e) => {
-                            send_response(stream, b"HTTP/1.1 500 INTERNAL SERVER ERROR\r\nContent-Type: text/plain; charset=UTF-8\r\n\r\nUnHealthy\r\n");
-                            println!("healthcheck error: {}", e)
+                            send_response(stream, &response::error_response("HTTP/1.1 401 UNAUTHORIZED", "invalid username or password")).await;
+                            println!("/login error: {}", e);
                         },
                     }
-                }
-                _ => {
-                    send_response(stream, b"HTTP/1.1 400 BAD REQUEST\r\nContent-Type: text/html; charset=UTF-8\r\n\r\n<html><body>400 Bad Request</body></html>\r\n");
-                    println!("default branch");
-                }
+                },
+                Err(e) => {
+                    send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "invalid JSON body")).await;
+                    println!("/login parse error: {}", e);
+                },
+            }
+        },
+        "/health" => {
+            match sqlx::query("SELECT 1").fetch_one(pool.as_ref()).await {
+                Ok(_) => {
+                    let body = response::json_body(&response::ApiResponse { data: MessageDto { message: "Healthy".to_string() } });
+                    send_response(stream, &response::json_response("HTTP/1.1 200 OK", &body)).await;
+                },
+                Err(e) => {
+                    send_response(stream, &response::error_response("HTTP/1.1 500 INTERNAL SERVER ERROR", "unhealthy")).await;
+                    println!("healthcheck error: {}", e)
+                },
             }
         }
-        Err(e) => println!("Unable to read stream: {}", e),
+        _ => {
+            send_response(stream, &response::error_response("HTTP/1.1 400 BAD REQUEST", "not found")).await;
+            println!("default branch");
+        }
     }
 }
 
 #[inline]
-fn send_response(mut stream: TcpStream, resp: &[u8]) {
-    match stream.write_all(resp) {
+async fn send_response(stream: &mut TcpStream, resp: &[u8]) {
+    match stream.write_all(resp).await {
         Err(e) => println!("Failed sending response: {}", e),
         Ok(_) => (),
     }
-    let _ = stream.flush();
+    let _ = stream.flush().await;
 }
 
 async fn new_conn(path: &str) -> Result<sqlx::Pool<Sqlite>, Box<dyn Error>> {
@@ -114,8 +234,9 @@ async fn new_conn(path: &str) -> Result<sqlx::Pool<Sqlite>, Box<dyn Error>> {
     }
 
     let pool = SqlitePool::connect(path).await.unwrap();
-    sqlx::query(r#"CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY NOT NULL, name VARCHAR(250) NOT NULL); 
-    CREATE INDEX IF NOT EXISTS users_id_idx ON users (id)"#).execute(&pool).await?;
+    sqlx::query(r#"CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY NOT NULL, name VARCHAR(250) NOT NULL, username TEXT UNIQUE, secret BLOB);
+    CREATE INDEX IF NOT EXISTS users_id_idx ON users (id);
+    CREATE TABLE IF NOT EXISTS sessions (token TEXT PRIMARY KEY NOT NULL, user_id INTEGER NOT NULL, expires_at INTEGER NOT NULL)"#).execute(&pool).await?;
 
     if is_empty {
         for i in 0..10000 {
@@ -130,25 +251,60 @@ async fn new_conn(path: &str) -> Result<sqlx::Pool<Sqlite>, Box<dyn Error>> {
 #[inline]
 fn parse_query_string(query: &str) -> HashMap<String, String> {
     let mut params = HashMap::new();
-    if let Some(query_str) = query.split('?').nth(1) {
-        for param in query_str.split('&') {
-            let mut key_value = param.split('=');
-            if let (Some(key), Some(value)) = (key_value.next(), key_value.next()) {
-                params.insert(key.to_string(), value.to_string());
-            }
+    for param in query.split('&') {
+        if param.is_empty() {
+            continue;
+        }
+        let mut key_value = param.split('=');
+        if let (Some(key), Some(value)) = (key_value.next(), key_value.next()) {
+            params.insert(key.to_string(), value.to_string());
         }
     }
     return params
 }
 
-#[derive(sqlx::FromRow)]
-struct User {
-    id: u32,
+#[derive(sqlx::FromRow, Clone)]
+pub(crate) struct User {
+    pub(crate) id: u32,
+    pub(crate) name: String,
+}
+
+/// Public, JSON-serializable shape of a [`User`] — the `id` is the
+/// Sqids-encoded public id, never the raw row id.
+#[derive(serde::Serialize)]
+struct UserDto {
+    id: String,
     name: String,
 }
 
-async fn worker(rx: Arc<Mutex<mpsc::Receiver<TcpStream>>>, db_pool: Arc<sqlx::Pool<Sqlite>>) {
-    while let Ok(stream) = rx.lock().await.recv() {
-        handle_connection(stream, db_pool.clone()).await;
+#[derive(serde::Deserialize)]
+struct NewUser {
+    name: String,
+}
+
+#[derive(serde::Serialize)]
+struct MessageDto {
+    message: String,
+}
+
+#[derive(serde::Deserialize)]
+struct RegisterRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(serde::Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+async fn worker(rx: Arc<tokio::sync::Mutex<mpsc::Receiver<TcpStream>>>, db_pool: Arc<sqlx::Pool<Sqlite>>, loader: Arc<Loader>) {
+    loop {
+        let stream = { rx.lock().await.recv().await };
+        match stream {
+            Some(stream) => handle_connection(stream, db_pool.clone(), Arc::clone(&loader)).await,
+            None => break,
+        }
     }
 }
@@ -0,0 +1,38 @@
+//! A thin JSON response layer: wraps a serializable payload in a small
+//! envelope and writes it out with a correct `Content-Length`, so handlers
+//! return typed values instead of hand-building response strings.
+
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct ApiResponse<T: Serialize> {
+    pub data: T,
+}
+
+#[derive(Serialize)]
+pub struct ApiErrorResponse {
+    pub error: String,
+}
+
+pub fn json_body<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_json::to_vec(value).unwrap_or_default()
+}
+
+pub fn json_response(status_line: &str, body: &[u8]) -> Vec<u8> {
+    json_response_with_headers(status_line, &[], body)
+}
+
+pub fn json_response_with_headers(status_line: &str, extra_headers: &[String], body: &[u8]) -> Vec<u8> {
+    let mut resp = format!("{}\r\n", status_line).into_bytes();
+    for header in extra_headers {
+        resp.extend_from_slice(header.as_bytes());
+        resp.extend_from_slice(b"\r\n");
+    }
+    resp.extend_from_slice(format!("Content-Type: application/json; charset=UTF-8\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes());
+    resp.extend_from_slice(body);
+    resp
+}
+
+pub fn error_response(status_line: &str, message: &str) -> Vec<u8> {
+    json_response(status_line, &json_body(&ApiErrorResponse { error: message.to_string() }))
+}
@@ -0,0 +1,92 @@
+//! Sqids-style encoder: turns internal auto-increment ids into short,
+//! non-sequential public strings (and back), so `/get` doesn't leak row
+//! counts or allow trivial enumeration.
+
+const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const SALT: &str = "crate-demo-salt";
+const SEPARATOR: char = '-';
+
+pub fn encode(numbers: &[u64]) -> String {
+    if numbers.is_empty() {
+        return String::new();
+    }
+
+    let alphabet: Vec<char> = ALPHABET.chars().collect();
+    let lottery_char = alphabet[(numbers[0] % alphabet.len() as u64) as usize];
+
+    let mut shuffled = shuffle(&alphabet, &format!("{}{}", lottery_char, SALT));
+    let mut out = String::new();
+    out.push(lottery_char);
+
+    for (i, &num) in numbers.iter().enumerate() {
+        out.push_str(&to_base(num, &shuffled));
+        if i + 1 < numbers.len() {
+            out.push(SEPARATOR);
+            shuffled = shuffle(&shuffled, &lottery_char.to_string());
+        }
+    }
+    out
+}
+
+pub fn decode(id: &str) -> Vec<u64> {
+    let lottery_char = match id.chars().next() {
+        Some(c) => c,
+        None => return Vec::new(),
+    };
+
+    let alphabet: Vec<char> = ALPHABET.chars().collect();
+    let mut shuffled = shuffle(&alphabet, &format!("{}{}", lottery_char, SALT));
+
+    let mut numbers = Vec::new();
+    for chunk in id[lottery_char.len_utf8()..].split(SEPARATOR) {
+        if chunk.is_empty() {
+            continue;
+        }
+        numbers.push(from_base(chunk, &shuffled));
+        shuffled = shuffle(&shuffled, &lottery_char.to_string());
+    }
+    numbers
+}
+
+/// Deterministic Fisher-Yates, driven by the seed's bytes instead of an RNG.
+fn shuffle(alphabet: &[char], seed: &str) -> Vec<char> {
+    let mut a = alphabet.to_vec();
+    let seed_bytes = seed.as_bytes();
+    if seed_bytes.is_empty() {
+        return a;
+    }
+
+    let n = a.len();
+    let mut j = 0usize;
+    for i in 0..n - 1 {
+        let r = seed_bytes[i % seed_bytes.len()] as usize;
+        j = (j + r + i) % n;
+        a.swap(i, j);
+    }
+    a
+}
+
+fn to_base(mut num: u64, alphabet: &[char]) -> String {
+    let base = alphabet.len() as u64;
+    if num == 0 {
+        return alphabet[0].to_string();
+    }
+
+    let mut digits = Vec::new();
+    while num > 0 {
+        digits.push(alphabet[(num % base) as usize]);
+        num /= base;
+    }
+    digits.iter().rev().collect()
+}
+
+fn from_base(s: &str, alphabet: &[char]) -> u64 {
+    let base = alphabet.len() as u64;
+    let mut num = 0u64;
+    for c in s.chars() {
+        if let Some(pos) = alphabet.iter().position(|&a| a == c) {
+            num = num * base + pos as u64;
+        }
+    }
+    num
+}